@@ -1,4 +1,4 @@
-use std::ffi::{c_void, CString};
+use std::ffi::{c_void, CStr, CString};
 use std::ptr;
 
 use gegl::EGLint;
@@ -8,21 +8,159 @@ use crate::{GlConfig, GlError};
 
 use glutin_egl_sys::{self as gegl, egl, egl::types::*};
 
+/// Returns whether `name` is present in the EGL display's extension string.
+unsafe fn has_egl_extension(egl: &egl::Egl, display: EGLDisplay, name: &str) -> bool {
+    let extensions = egl.QueryString(display, egl::EXTENSIONS as EGLint);
+    if extensions.is_null() {
+        return false;
+    }
+    CStr::from_ptr(extensions)
+        .to_string_lossy()
+        .split_whitespace()
+        .any(|ext| ext == name)
+}
+
+/// `EGL_RENDERABLE_TYPE`/`EGL_CONFORMANT` bit for the requested `Api`.
+fn renderable_type_bit(api: crate::Api) -> EGLenum {
+    let bit = match api {
+        crate::Api::Gl => egl::OPENGL_BIT,
+        crate::Api::Gles => egl::OPENGL_ES2_BIT,
+    };
+    bit as EGLenum
+}
+
+/// Opens the default EGL display, binds the requested client API and checks
+/// that robustness, if requested, is actually supported. Shared by `create`
+/// and `create_offscreen` since both need the exact same display/API setup
+/// before diverging on surface type.
+unsafe fn init_display(egl: &egl::Egl, conf: &GlConfig) -> Result<EGLDisplay, GlError> {
+    let display = egl.GetDisplay(egl::DEFAULT_DISPLAY as *const _);
+    if display == egl::NO_DISPLAY {
+        log::error!("eglGetDisplay return NO_DISPLAY");
+        return Err(GlError::CreationFailed);
+    }
+
+    let mut major = 0;
+    let mut minor = 0;
+    if egl.Initialize(display, &mut major, &mut minor) == egl::FALSE {
+        log::error!("eglInitialize failed: {}", egl.GetError());
+        return Err(GlError::CreationFailed);
+    }
+
+    log::info!("initialized EGL: version {}.{}", major, minor);
+
+    let api = match conf.api {
+        crate::Api::Gl => egl::OPENGL_API,
+        crate::Api::Gles => egl::OPENGL_ES_API,
+    };
+    if egl.BindAPI(api) == egl::FALSE {
+        log::error!("eglBindAPI failed: {}", egl.GetError());
+        egl.Terminate(display);
+        return Err(GlError::ApiNotSupported);
+    }
+
+    if conf.robustness != crate::Robustness::NotRobust
+        && !has_egl_extension(egl, display, "EGL_EXT_create_context_robustness")
+    {
+        log::error!("EGL_EXT_create_context_robustness is not supported");
+        egl.Terminate(display);
+        return Err(GlError::RobustnessNotSupported);
+    }
+
+    Ok(display)
+}
+
+/// Runs `eglChooseConfig` against `attribs` and returns the matching configs,
+/// most-preferred first, as `eglChooseConfig` itself ranks them.
+unsafe fn choose_configs(
+    egl: &egl::Egl,
+    display: EGLDisplay,
+    attribs: &[EGLenum],
+) -> Result<Vec<EGLConfig>, GlError> {
+    let mut config: [EGLConfig; 64] = [ptr::null(); 64];
+    let mut num_config: EGLint = 0;
+    if egl.ChooseConfig(
+        display,
+        attribs.as_ptr() as *const EGLint,
+        config.as_mut_ptr(),
+        64,
+        &mut num_config,
+    ) == egl::FALSE
+    {
+        log::error!("eglChooseConfig failed: {}", egl.GetError());
+        return Err(GlError::CreationFailed);
+    }
+
+    if num_config == 0 {
+        log::error!("eglChooseConfig returned 0 configs");
+        return Err(GlError::CreationFailed);
+    }
+    log::info!("eglChooseConfig returned {} configs", num_config);
+
+    Ok(config[..num_config as usize].to_vec())
+}
+
+/// Builds the `eglCreateContext` attrib list for `conf`, including the
+/// `EGL_EXT_create_context_robustness` attribs if robustness was requested.
+fn build_ctx_attribs(conf: &GlConfig) -> Vec<EGLenum> {
+    #[rustfmt::skip]
+    let mut ctx_attribs = vec![
+        egl::CONTEXT_MAJOR_VERSION, conf.version.0 as EGLenum,
+        egl::CONTEXT_MINOR_VERSION, conf.version.1 as EGLenum,
+    ];
+
+    #[rustfmt::skip]
+    match conf.robustness {
+        crate::Robustness::NotRobust => {}
+        crate::Robustness::RobustNoResetNotification => {
+            ctx_attribs.extend_from_slice(&[
+                egl::CONTEXT_OPENGL_ROBUST_ACCESS_EXT, egl::TRUE as EGLenum,
+                egl::CONTEXT_RESET_NOTIFICATION_STRATEGY_EXT, egl::NO_RESET_NOTIFICATION_EXT as EGLenum,
+            ]);
+        }
+        crate::Robustness::RobustLoseContextOnReset => {
+            ctx_attribs.extend_from_slice(&[
+                egl::CONTEXT_OPENGL_ROBUST_ACCESS_EXT, egl::TRUE as EGLenum,
+                egl::CONTEXT_RESET_NOTIFICATION_STRATEGY_EXT, egl::LOSE_CONTEXT_ON_RESET_EXT as EGLenum,
+            ]);
+        }
+    }
+
+    ctx_attribs.push(egl::NONE);
+    ctx_attribs
+}
+
+/// Describes the actual framebuffer layout an `EGLConfig` was given, as
+/// opposed to the bits requested through `GlConfig` (which are only hints to
+/// `eglChooseConfig`), mirroring glutin's `PixelFormat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelFormat {
+    pub red_bits: u8,
+    pub green_bits: u8,
+    pub blue_bits: u8,
+    pub alpha_bits: u8,
+    pub depth_bits: u8,
+    pub stencil_bits: u8,
+    pub sample_buffers: u8,
+    pub samples: u8,
+}
+
 pub struct GlContext {
     display: EGLDisplay,
     context: EGLContext,
     surface: EGLSurface,
+    /// The `EGLConfig` that was actually chosen by `eglChooseConfig`.
+    config: EGLConfig,
+    /// `true` when `create_offscreen` created this context without any
+    /// `EGLSurface`, relying on `EGL_KHR_surfaceless_context` instead of a
+    /// pbuffer. `destroy_surface` does not set it.
+    surfaceless: bool,
 }
 impl GlContext {
     pub unsafe fn create(
         parent: &impl HasRawWindowHandle,
         conf: GlConfig,
     ) -> Result<GlContext, GlError> {
-        match conf.api {
-            crate::Api::Gl => return Err(GlError::ApiNotSupported),
-            crate::Api::Gles => {}
-        }
-
         let handle = if let RawWindowHandle::AndroidNdk(handle) = parent.raw_window_handle() {
             handle
         } else {
@@ -35,11 +173,13 @@ impl GlContext {
             return Err(GlError::InvalidWindowHandle);
         }
 
+        let renderable_type = renderable_type_bit(conf.api);
+
         #[rustfmt::skip]
         let attribs = [
             egl::SURFACE_TYPE, egl::WINDOW_BIT as EGLenum,
-            egl::RENDERABLE_TYPE, egl::OPENGL_ES2_BIT as EGLenum,
-            egl::CONFORMANT, egl::OPENGL_ES2_BIT as EGLenum,
+            egl::RENDERABLE_TYPE, renderable_type,
+            egl::CONFORMANT, renderable_type,
             egl::RED_SIZE, conf.red_bits as EGLenum,
             egl::GREEN_SIZE, conf.green_bits as EGLenum,
             egl::BLUE_SIZE, conf.blue_bits as EGLenum,
@@ -56,52 +196,23 @@ impl GlContext {
             display: egl::NO_DISPLAY,
             context: egl::NO_CONTEXT,
             surface: egl::NO_SURFACE,
+            config: ptr::null(),
+            surfaceless: false,
         };
 
         let egl = egl::Egl;
 
-        this.display = egl.GetDisplay(egl::DEFAULT_DISPLAY as *const _);
-        if this.display == egl::NO_DISPLAY {
-            log::error!("eglGetDisplay return NO_DISPLAY");
-            return Err(GlError::CreationFailed);
-        }
-
-        let mut major = 0;
-        let mut minor = 0;
-        if egl.Initialize(this.display, &mut major, &mut minor) == egl::FALSE {
-            log::error!("eglInitialize failed: {}", egl.GetError());
-            return Err(GlError::CreationFailed);
-        }
-
-        log::info!("initialized EGL: version {}.{}", major, minor);
+        this.display = init_display(&egl, &conf)?;
 
-        let mut config: [EGLConfig; 64] = [ptr::null(); 64];
-        let mut num_config: EGLint = 0;
-        if egl.ChooseConfig(
-            this.display,
-            attribs.as_ptr() as *const EGLint,
-            config.as_mut_ptr(),
-            64,
-            &mut num_config,
-        ) == egl::FALSE
-        {
-            log::error!("eglChooseConfig failed: {}", egl.GetError());
-            return Err(GlError::CreationFailed);
-        }
-
-        if num_config == 0 {
-            log::error!("eglChooseConfig returned 0 configs");
-            return Err(GlError::CreationFailed);
-        }
-        log::info!("eglChooseConfig returned {} configs", num_config);
+        let configs = choose_configs(&egl, this.display, &attribs)?;
 
         let window = handle.a_native_window;
 
-        let mut configs = config[..num_config as usize].iter();
+        let mut configs = configs.into_iter();
         let mut config: EGLConfig;
         loop {
             config = match configs.next() {
-                Some(x) => *x,
+                Some(x) => x,
                 None => {
                     log::error!("all configs failed");
                     return Err(GlError::CreationFailed);
@@ -132,13 +243,9 @@ impl GlContext {
             break;
         }
 
-        #[rustfmt::skip]
-        let ctx_attribs = [ 
-            // request a context using Open GL ES 2.0
-            egl::CONTEXT_MAJOR_VERSION, conf.version.0 as EGLenum, 
-            egl::CONTEXT_MINOR_VERSION, conf.version.1 as EGLenum, 
-            egl::NONE 
-        ];
+        this.config = config;
+
+        let ctx_attribs = build_ctx_attribs(&conf);
 
         let shared_context = conf
             .share
@@ -156,6 +263,201 @@ impl GlContext {
         Ok(this)
     }
 
+    /// Creates a headless context that renders into an offscreen pbuffer
+    /// surface instead of a window, for background GL work (render-to-FBO,
+    /// compute, screenshotting) where no `ANativeWindow` is available.
+    ///
+    /// If the display advertises `EGL_KHR_surfaceless_context`, no surface is
+    /// created at all and the context is made current with `EGL_NO_SURFACE`.
+    pub unsafe fn create_offscreen(
+        conf: GlConfig,
+        width: i32,
+        height: i32,
+    ) -> Result<GlContext, GlError> {
+        let renderable_type = renderable_type_bit(conf.api);
+
+        let mut this = GlContext {
+            display: egl::NO_DISPLAY,
+            context: egl::NO_CONTEXT,
+            surface: egl::NO_SURFACE,
+            config: ptr::null(),
+            surfaceless: false,
+        };
+
+        let egl = egl::Egl;
+
+        this.display = init_display(&egl, &conf)?;
+
+        let surfaceless_supported = has_egl_extension(&egl, this.display, "EGL_KHR_surfaceless_context");
+
+        // A surfaceless context needs no surface capability at all, so don't
+        // require EGL_PBUFFER_BIT in that case: a driver could otherwise
+        // advertise EGL_KHR_surfaceless_context on configs that don't expose
+        // EGL_PBUFFER_BIT, and those would wrongly get filtered out.
+        let surface_type = if surfaceless_supported { 0 } else { egl::PBUFFER_BIT as EGLenum };
+
+        #[rustfmt::skip]
+        let attribs = [
+            egl::SURFACE_TYPE, surface_type,
+            egl::RENDERABLE_TYPE, renderable_type,
+            egl::CONFORMANT, renderable_type,
+            egl::RED_SIZE, conf.red_bits as EGLenum,
+            egl::GREEN_SIZE, conf.green_bits as EGLenum,
+            egl::BLUE_SIZE, conf.blue_bits as EGLenum,
+            egl::ALPHA_SIZE, conf.alpha_bits as EGLenum,
+            egl::DEPTH_SIZE, conf.depth_bits as EGLenum,
+            egl::STENCIL_SIZE, conf.stencil_bits as EGLenum,
+            egl::SAMPLE_BUFFERS, conf.samples.is_some() as EGLenum,
+            egl::SAMPLES, conf.samples.unwrap_or(0) as EGLenum,
+            egl::NONE,
+        ];
+
+        let configs = choose_configs(&egl, this.display, &attribs)?;
+
+        let ctx_attribs = build_ctx_attribs(&conf);
+        let shared_context = conf
+            .share
+            .map(|x| x.context.context)
+            .unwrap_or(egl::NO_CONTEXT);
+
+        let mut configs = configs.into_iter();
+        loop {
+            let config = match configs.next() {
+                Some(x) => x,
+                None => {
+                    log::error!("all configs failed");
+                    return Err(GlError::CreationFailed);
+                }
+            };
+
+            if surfaceless_supported {
+                this.surface = egl::NO_SURFACE;
+            } else {
+                #[rustfmt::skip]
+                let pbuffer_attribs = [
+                    egl::WIDTH, width as EGLenum,
+                    egl::HEIGHT, height as EGLenum,
+                    egl::NONE,
+                ];
+
+                this.surface = egl.CreatePbufferSurface(
+                    this.display,
+                    config,
+                    pbuffer_attribs.as_ptr() as *const EGLint,
+                );
+
+                if this.surface == egl::NO_SURFACE {
+                    let error = egl.GetError();
+                    log::error!(
+                        "eglCreatePbufferSurface failed: {} ({})",
+                        match error as _ {
+                            egl::BAD_DISPLAY => "EGL_BAD_DISPLAY",
+                            egl::NOT_INITIALIZED => "EGL_NOT_INITIALIZED",
+                            egl::BAD_CONFIG => "EGL_BAD_CONFIG",
+                            egl::BAD_ATTRIBUTE => "EGL_BAD_ATTRIBUTE",
+                            egl::BAD_ALLOC => "EGL_BAD_ALLOC",
+                            egl::BAD_MATCH => "EGL_BAD_MATCH",
+                            _ => "Other",
+                        },
+                        error
+                    );
+                    continue;
+                }
+            }
+
+            this.context = egl.CreateContext(
+                this.display,
+                config,
+                shared_context,
+                ctx_attribs.as_ptr() as *const EGLint,
+            );
+
+            if this.context == egl::NO_CONTEXT {
+                log::error!("eglCreateContext failed: {}", egl.GetError());
+                if this.surface != egl::NO_SURFACE {
+                    egl.DestroySurface(this.display, this.surface);
+                    this.surface = egl::NO_SURFACE;
+                }
+                continue;
+            }
+
+            this.config = config;
+            this.surfaceless = surfaceless_supported;
+            break;
+        }
+
+        this.make_current();
+
+        Ok(this)
+    }
+
+    /// Tears down the `EGLSurface` without destroying the `EGLContext` or any
+    /// GL resources held by it.
+    ///
+    /// This is needed on Android, where the `ANativeWindow` is destroyed and
+    /// recreated across `surfaceDestroyed`/`surfaceCreated` activity
+    /// lifecycle callbacks, but the GL context and its objects must survive
+    /// the window going away. Call [`Self::recreate_surface`] once a new
+    /// window is available.
+    pub unsafe fn destroy_surface(&mut self) {
+        if self.surface == egl::NO_SURFACE {
+            return;
+        }
+
+        let egl = egl::Egl;
+
+        self.make_not_current();
+
+        log::debug!("eglDestroySurface");
+        egl.DestroySurface(self.display, self.surface);
+        self.surface = egl::NO_SURFACE;
+    }
+
+    /// Recreates the `EGLSurface` against the `EGLConfig` chosen at
+    /// creation time, for a window handed back by `surfaceCreated` after
+    /// [`Self::destroy_surface`] was called, and makes the context current
+    /// again.
+    pub unsafe fn recreate_surface(
+        &mut self,
+        parent: &impl HasRawWindowHandle,
+    ) -> Result<(), GlError> {
+        let handle = if let RawWindowHandle::AndroidNdk(handle) = parent.raw_window_handle() {
+            handle
+        } else {
+            log::error!("invalid window handle: {:?}", parent.raw_window_handle());
+            return Err(GlError::InvalidWindowHandle);
+        };
+
+        if handle.a_native_window.is_null() {
+            log::error!("window handle is null");
+            return Err(GlError::InvalidWindowHandle);
+        }
+
+        // Destroy any surface left over from a previous call, or from a
+        // `surfaceChanged` that wasn't preceded by `destroy_surface`, so it
+        // isn't leaked.
+        self.destroy_surface();
+
+        let egl = egl::Egl;
+
+        self.surface = egl.CreateWindowSurface(
+            self.display,
+            self.config,
+            handle.a_native_window,
+            ptr::null(),
+        );
+
+        if self.surface == egl::NO_SURFACE {
+            log::error!("eglCreateWindowSurface failed: {}", egl.GetError());
+            return Err(GlError::CreationFailed);
+        }
+
+        self.surfaceless = false;
+        self.make_current();
+
+        Ok(())
+    }
+
     pub unsafe fn make_current(&self) {
         let egl = egl::Egl;
 
@@ -182,7 +484,55 @@ impl GlContext {
         unsafe { egl.GetProcAddress(symbol.as_ptr()) as *const c_void }
     }
 
+    /// Returns the actual framebuffer layout of the `EGLConfig` this context
+    /// was created with, which may differ from the bits requested in
+    /// `GlConfig` since those are only hints to `eglChooseConfig`.
+    pub fn get_pixel_format(&self) -> PixelFormat {
+        let egl = egl::Egl;
+
+        let attrib = |attribute: EGLenum| -> u8 {
+            let mut value: EGLint = 0;
+            unsafe {
+                if egl.GetConfigAttrib(self.display, self.config, attribute as EGLint, &mut value)
+                    == egl::FALSE
+                {
+                    log::error!("eglGetConfigAttrib failed: {}", egl.GetError());
+                }
+            }
+            value as u8
+        };
+
+        PixelFormat {
+            red_bits: attrib(egl::RED_SIZE),
+            green_bits: attrib(egl::GREEN_SIZE),
+            blue_bits: attrib(egl::BLUE_SIZE),
+            alpha_bits: attrib(egl::ALPHA_SIZE),
+            depth_bits: attrib(egl::DEPTH_SIZE),
+            stencil_bits: attrib(egl::STENCIL_SIZE),
+            sample_buffers: attrib(egl::SAMPLE_BUFFERS),
+            samples: attrib(egl::SAMPLES),
+        }
+    }
+
+    /// Sets the minimum number of video frame periods per buffer swap,
+    /// e.g. `1` for vsync-on, `0` for immediate/tearing swaps. Requires the
+    /// context to be current, which it already is right after `create`.
+    pub fn set_swap_interval(&self, interval: i32) {
+        let egl = egl::Egl;
+        unsafe {
+            if egl.SwapInterval(self.display, interval as EGLint) == egl::FALSE {
+                log::error!("eglSwapInterval failed: {}", egl.GetError());
+            }
+        }
+    }
+
     pub fn swap_buffers(&self) {
+        if self.surfaceless {
+            // There is no surface to present; surfaceless contexts are
+            // expected to render to an FBO and read it back instead.
+            return;
+        }
+
         let egl = egl::Egl;
         unsafe {
             if egl.SwapBuffers(self.display, self.surface) == egl::FALSE {